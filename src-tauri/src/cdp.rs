@@ -0,0 +1,187 @@
+// Drives a real Chrome/Edge window over the Chrome DevTools Protocol so the OAuth
+// redirect can be observed without relying on a listening loopback port — needed for
+// flows like SIMKL's PIN page, or any provider that returns the code in a URL
+// fragment or page title instead of a query string a loopback server can capture.
+
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use crate::AnyErr;
+
+pub struct CdpAuthResult {
+  pub code: Option<String>,
+  pub state: Option<String>,
+}
+
+fn free_port() -> Result<u16, AnyErr> {
+  let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+  Ok(listener.local_addr()?.port())
+}
+
+async fn wait_for_page_ws_url(client: &reqwest::Client, debug_port: u16, deadline: tokio::time::Instant) -> Result<String, AnyErr> {
+  let targets_url = format!("http://127.0.0.1:{debug_port}/json");
+  loop {
+    if tokio::time::Instant::now() >= deadline {
+      return Err("CDP endpoint did not come up in time".into());
+    }
+    if let Ok(resp) = client.get(&targets_url).send().await {
+      if resp.status().is_success() {
+        if let Ok(targets) = resp.json::<Vec<Value>>().await {
+          let page = targets.iter().find(|t| t.get("type").and_then(|x| x.as_str()) == Some("page"));
+          if let Some(ws) = page.and_then(|t| t.get("webSocketDebuggerUrl")).and_then(|x| x.as_str()) {
+            return Ok(ws.to_string());
+          }
+        }
+      }
+    }
+    tokio::time::sleep(Duration::from_millis(200)).await;
+  }
+}
+
+// Looks for `code`/`state` in both the query string and the URL fragment — some
+// providers return them after the `#` instead, which a plain loopback server
+// listening for a query string would never see.
+fn extract_code_state(url: &str) -> Option<(Option<String>, Option<String>)> {
+  let parsed = url::Url::parse(url).ok()?;
+  let mut code = parsed.query_pairs().find(|(k, _)| k == "code").map(|(_, v)| v.to_string());
+  let mut state = parsed.query_pairs().find(|(k, _)| k == "state").map(|(_, v)| v.to_string());
+
+  if let Some(fragment) = parsed.fragment() {
+    for (k, v) in url::form_urlencoded::parse(fragment.as_bytes()) {
+      if code.is_none() && k == "code" {
+        code = Some(v.to_string());
+      }
+      if state.is_none() && k == "state" {
+        state = Some(v.to_string());
+      }
+    }
+  }
+
+  Some((code, state))
+}
+
+// Some providers never put the code in the URL at all — they render it as plain
+// text in the page title instead (e.g. "Login successful — code: ABC123"). This is
+// necessarily best-effort since titles aren't a structured format: require `code`/
+// `state` to be followed by a `:`/`=` separator (not just the substring appearing
+// anywhere, which would also match incidental text like an unrelated "enter the
+// verification code" interstitial), then take the token after it.
+fn extract_code_state_from_title(title: &str) -> Option<(Option<String>, Option<String>)> {
+  fn find_value(title: &str, key: &str) -> Option<String> {
+    let idx = title.to_ascii_lowercase().find(key)?;
+    let after = &title[idx + key.len()..];
+    let after = after.trim_start_matches(char::is_whitespace);
+    if !after.starts_with(':') && !after.starts_with('=') {
+      return None;
+    }
+    let after = &after[1..];
+    let after = after.trim_start_matches(char::is_whitespace);
+    let value: String = after.chars().take_while(|c| c.is_alphanumeric() || *c == '-' || *c == '_' || *c == '.').collect();
+    if value.is_empty() { None } else { Some(value) }
+  }
+
+  let code = find_value(title, "code")?;
+  let state = find_value(title, "state");
+  Some((Some(code), state))
+}
+
+/// Launches `browser_path` pointed at `authorize_url` with a fresh debuggable profile
+/// sized `width`x`height`, watches navigation/network events until one targets
+/// `redirect_uri` with a `code` in it (or the polled page title carries one), then
+/// closes it.
+pub async fn run_cdp_auth(browser_path: &str, authorize_url: &str, redirect_uri: &str, width: u32, height: u32, timeout: Duration) -> Result<CdpAuthResult, AnyErr> {
+  let debug_port = free_port()?;
+  let profile_dir = std::env::temp_dir().join(format!("crosswatch-cdp-{debug_port}"));
+  std::fs::create_dir_all(&profile_dir)?;
+
+  let mut child = std::process::Command::new(browser_path)
+    .arg(format!("--remote-debugging-port={debug_port}"))
+    .arg(format!("--user-data-dir={}", profile_dir.display()))
+    .arg("--no-first-run")
+    .arg(format!("--window-size={width},{height}"))
+    .arg(authorize_url)
+    .spawn()?;
+
+  let deadline = tokio::time::Instant::now() + timeout;
+  let result = run_cdp_session(debug_port, redirect_uri, deadline).await;
+
+  let _ = child.kill();
+  let _ = child.wait();
+  let _ = std::fs::remove_dir_all(&profile_dir);
+  result
+}
+
+const TITLE_POLL_INTERVAL: Duration = Duration::from_millis(750);
+
+async fn run_cdp_session(debug_port: u16, redirect_uri: &str, deadline: tokio::time::Instant) -> Result<CdpAuthResult, AnyErr> {
+  use futures_util::{SinkExt, StreamExt};
+  use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+  let http = reqwest::Client::new();
+  let ws_url = wait_for_page_ws_url(&http, debug_port, deadline).await?;
+  let (ws_stream, _) = connect_async(&ws_url).await?;
+  let (mut write, mut read) = ws_stream.split();
+
+  let mut next_id: u64 = 1;
+  for method in ["Page.enable", "Network.enable", "Runtime.enable"] {
+    write.send(Message::Text(json!({"id": next_id, "method": method, "params": {}}).to_string())).await?;
+    next_id += 1;
+  }
+
+  // Tracks the id of our most recent `document.title` poll so we can recognize its
+  // response among the other CDP event traffic on the same socket.
+  let mut pending_title_poll: Option<u64> = None;
+  let mut last_title_poll = tokio::time::Instant::now();
+
+  loop {
+    if tokio::time::Instant::now() >= deadline {
+      return Err("CDP auth timed out waiting for redirect".into());
+    }
+
+    // Actively poll the page title in addition to reacting to navigation events —
+    // covers providers that embed the code in the title instead of the URL.
+    if pending_title_poll.is_none() && last_title_poll.elapsed() >= TITLE_POLL_INTERVAL {
+      let id = next_id;
+      next_id += 1;
+      write.send(Message::Text(json!({
+        "id": id,
+        "method": "Runtime.evaluate",
+        "params": {"expression": "document.title", "returnByValue": true}
+      }).to_string())).await?;
+      pending_title_poll = Some(id);
+      last_title_poll = tokio::time::Instant::now();
+    }
+
+    let next = match tokio::time::timeout(Duration::from_millis(250), read.next()).await {
+      Ok(Some(Ok(msg))) => msg,
+      Ok(Some(Err(e))) => return Err(format!("CDP websocket error: {e}").into()),
+      Ok(None) => return Err("CDP websocket closed before redirect was observed".into()),
+      Err(_) => continue, // poll timed out, check deadline and retry
+    };
+    let Message::Text(text) = next else { continue };
+    let Ok(ev) = serde_json::from_str::<Value>(&text) else { continue };
+
+    if pending_title_poll == ev.get("id").and_then(|x| x.as_u64()) {
+      pending_title_poll = None;
+      if let Some(title) = ev.pointer("/result/result/value").and_then(|x| x.as_str()) {
+        if let Some((code, state)) = extract_code_state_from_title(title) {
+          return Ok(CdpAuthResult { code, state });
+        }
+      }
+      continue;
+    }
+
+    let url = match ev.get("method").and_then(|x| x.as_str()) {
+      Some("Page.frameNavigated") => ev.pointer("/params/frame/url").and_then(|x| x.as_str()),
+      Some("Network.requestWillBeSent") => ev.pointer("/params/request/url").and_then(|x| x.as_str()),
+      _ => None,
+    };
+    let Some(url) = url else { continue };
+    if !url.starts_with(redirect_uri) {
+      continue;
+    }
+    if let Some((code @ Some(_), state)) = extract_code_state(url) {
+      return Ok(CdpAuthResult { code, state });
+    }
+  }
+}
@@ -1,5 +1,8 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod cdp;
+mod secrets;
+
 use std::{fs, path::{Path, PathBuf}};
 
 use tauri::{AppHandle, Emitter};
@@ -7,18 +10,25 @@ use serde::{Serialize, Deserialize};
 use tauri_plugin_shell::ShellExt;
 use tauri_plugin_opener::OpenerExt;
 use reqwest::header::{HeaderMap, HeaderValue};
+use secrets::SecretToken;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 struct PlexCfg {
-  #[serde(default)] account_token: Option<String>,
+  #[serde(skip)] account_token: SecretToken,
+  // Read-only presence flags for the frontend: the actual token is never sent over
+  // IPC, so the UI has no other way to tell "linked" from "not linked" when it
+  // round-trips a `Config` it read back through `cmd_write_config`.
+  #[serde(default)] has_account_token: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 struct SimklCfg {
   #[serde(default)] client_id: String,
   #[serde(default)] client_secret: String,
-  #[serde(default)] access_token: Option<String>,
-  #[serde(default)] refresh_token: Option<String>,
+  #[serde(skip)] access_token: SecretToken,
+  #[serde(skip)] refresh_token: SecretToken,
+  #[serde(default)] has_access_token: bool,
+  #[serde(default)] has_refresh_token: bool,
   #[serde(default)] token_expires_at: Option<i64>,
 }
 
@@ -104,21 +114,67 @@ fn cfg_path() -> PathBuf {
   base.join("config.json")
 }
 
+// Decrypts a single token field, isolating failures to that field: a corrupt
+// envelope or an unreachable data key clears just that secret (forcing the
+// user to re-link that one provider) instead of failing the whole config load
+// and taking unrelated settings (sync/runtime) down with it.
+fn load_secret_field(raw: Option<&str>, label: &str) -> (SecretToken, bool) {
+  match SecretToken::from_raw(raw) {
+    Ok(result) => result,
+    Err(e) => {
+      eprintln!("clearing {label} (re-login required): {e}");
+      (SecretToken::none(), false)
+    }
+  }
+}
+
+// Parses a config JSON string, decrypting token envelopes (or migrating
+// legacy plaintext tokens in memory so the next `write_cfg` re-encrypts them).
+fn parse_cfg_str(s: &str) -> Result<(Config, bool), AnyErr> {
+  let raw: serde_json::Value = serde_json::from_str(s)?;
+  let mut cfg: Config = serde_json::from_value(raw.clone())?;
+  let mut migrated = false;
+
+  if let Some(plex) = raw.get("plex") {
+    let (token, was_plaintext) = load_secret_field(plex.get("account_token").and_then(|v| v.as_str()), "plex.account_token");
+    let p = cfg.plex.get_or_insert_with(Default::default);
+    p.has_account_token = token.expose().is_some();
+    p.account_token = token;
+    migrated |= was_plaintext;
+  }
+  if let Some(simkl) = raw.get("simkl") {
+    let s = cfg.simkl.get_or_insert_with(Default::default);
+    let (access, was_plaintext) = load_secret_field(simkl.get("access_token").and_then(|v| v.as_str()), "simkl.access_token");
+    s.has_access_token = access.expose().is_some();
+    s.access_token = access;
+    migrated |= was_plaintext;
+    let (refresh, was_plaintext) = load_secret_field(simkl.get("refresh_token").and_then(|v| v.as_str()), "simkl.refresh_token");
+    s.has_refresh_token = refresh.expose().is_some();
+    s.refresh_token = refresh;
+    migrated |= was_plaintext;
+  }
+
+  Ok((cfg, migrated))
+}
+
 fn read_cfg() -> Result<Config, AnyErr> {
   let p = cfg_path();
   if p.exists() {
     let s = fs::read_to_string(p)?;
-    let cfg: Config = serde_json::from_str(&s)?;
+    let (cfg, migrated) = parse_cfg_str(&s)?;
+    if migrated {
+      write_cfg(&cfg)?;
+    }
     return Ok(cfg);
   }
   for cand in legacy_cfg_candidates() {
     if cand.exists() {
       let s = fs::read_to_string(&cand)?;
-      let cfg: Config = serde_json::from_str(&s)?;
+      let (cfg, _migrated) = parse_cfg_str(&s)?;
       let newp = cfg_path();
       if let Some(dir) = newp.parent() { let _ = fs::create_dir_all(dir); }
-      fs::write(&newp, &s)?;
-      let _ = fs::write(project_root_cfg(), &s);
+      // Always write back through write_cfg so legacy plaintext tokens are encrypted.
+      write_cfg(&cfg)?;
       if cand.to_string_lossy().contains("src-tauri") {
         let _ = fs::remove_file(&cand);
       }
@@ -128,10 +184,40 @@ fn read_cfg() -> Result<Config, AnyErr> {
   Ok(Config::default())
 }
 
+// Encrypts `tok` and stores it under `section.field`, or — if `tok` is absent —
+// carries over whatever envelope is already on disk for that field. The incoming
+// `Config` almost never carries a fresh token (the secret fields are `#[serde(skip)]`,
+// so a `Config` round-tripped by the frontend through `cmd_read_config` always has
+// them empty): without this fallback, any settings save would silently erase every
+// already-linked token.
+fn write_token_field(value: &mut serde_json::Value, existing: Option<&serde_json::Value>, section: &str, field: &str, tok: Option<&str>) -> Result<(), AnyErr> {
+  let envelope = match tok {
+    Some(tok) => secrets::encrypt(tok)?,
+    None => match existing.and_then(|e| e.get(section)).and_then(|s| s.get(field)).and_then(|v| v.as_str()) {
+      Some(envelope) => envelope.to_string(),
+      None => return Ok(()),
+    },
+  };
+  set_nested_str(value, section, field, envelope);
+  Ok(())
+}
+
 fn write_cfg(cfg: &Config) -> Result<(), AnyErr> {
   let p = cfg_path();
   if let Some(dir) = p.parent() { let _ = fs::create_dir_all(dir); }
-  let s = serde_json::to_string_pretty(cfg)?;
+
+  let existing: Option<serde_json::Value> = fs::read_to_string(&p).ok().and_then(|s| serde_json::from_str(&s).ok());
+
+  let mut value = serde_json::to_value(cfg)?;
+  if cfg.plex.is_some() {
+    write_token_field(&mut value, existing.as_ref(), "plex", "account_token", cfg.plex.as_ref().and_then(|p| p.account_token.expose()))?;
+  }
+  if cfg.simkl.is_some() {
+    write_token_field(&mut value, existing.as_ref(), "simkl", "access_token", cfg.simkl.as_ref().and_then(|s| s.access_token.expose()))?;
+    write_token_field(&mut value, existing.as_ref(), "simkl", "refresh_token", cfg.simkl.as_ref().and_then(|s| s.refresh_token.expose()))?;
+  }
+
+  let s = serde_json::to_string_pretty(&value)?;
   fs::write(&p, &s)?;
   let _ = fs::write(project_root_cfg(), &s);
   for cand in legacy_cfg_candidates() {
@@ -142,6 +228,12 @@ fn write_cfg(cfg: &Config) -> Result<(), AnyErr> {
   Ok(())
 }
 
+fn set_nested_str(value: &mut serde_json::Value, section: &str, field: &str, s: String) {
+  if let Some(obj) = value.get_mut(section).and_then(|v| v.as_object_mut()) {
+    obj.insert(field.to_string(), serde_json::Value::String(s));
+  }
+}
+
 // ---------------- Utilities ----------------
 
 #[tauri::command]
@@ -159,41 +251,66 @@ async fn cmd_open_url(app: AppHandle, url: String) -> Result<(), String> {
   app.opener().open_url(url, None::<String>).map_err(|e| e.to_string())
 }
 
-#[tauri::command]
-async fn cmd_open_external_sized(app: AppHandle, url: String, width: Option<u32>, height: Option<u32>) -> Result<(), String> {
-  let w = width.unwrap_or(520);
-  let h = height.unwrap_or(720);
-
-  // Edge
+// Finds an installed Chromium-family browser we can either shell out to or drive
+// via the DevTools protocol. Edge is preferred since it ships with Windows.
+fn find_browser_path() -> Option<String> {
   let edge_candidates = [
     std::env::var("PROGRAMFILES(X86)").ok().map(|p| format!(r"{}\Microsoft\Edge\Application\msedge.exe", p)),
     std::env::var("PROGRAMFILES").ok().map(|p| format!(r"{}\Microsoft\Edge\Application\msedge.exe", p)),
   ];
-  let args_edge = vec!["--new-window".into(), format!("--window-size={},{}", w, h), url.clone()];
-  for p in edge_candidates.into_iter().flatten() {
-    if Path::new(&p).exists() {
-      AppHandle::shell(&app).command(p).args(args_edge.clone()).spawn().map_err(|e| e.to_string())?;
-      return Ok(());
-    }
-  }
-
-  // Chrome
   let chrome_candidates = [
     std::env::var("PROGRAMFILES(X86)").ok().map(|p| format!(r"{}\Google\Chrome\Application\chrome.exe", p)),
     std::env::var("PROGRAMFILES").ok().map(|p| format!(r"{}\Google\Chrome\Application\chrome.exe", p)),
   ];
-  let args_chrome = vec!["--new-window".into(), format!("--window-size={},{}", w, h), url.clone()];
-  for p in chrome_candidates.into_iter().flatten() {
-    if Path::new(&p).exists() {
-      AppHandle::shell(&app).command(p).args(args_chrome.clone()).spawn().map_err(|e| e.to_string())?;
-      return Ok(());
-    }
+  edge_candidates.into_iter().chain(chrome_candidates)
+    .flatten()
+    .find(|p| Path::new(p).exists())
+}
+
+#[tauri::command]
+async fn cmd_open_external_sized(app: AppHandle, url: String, width: Option<u32>, height: Option<u32>) -> Result<(), String> {
+  let w = width.unwrap_or(520);
+  let h = height.unwrap_or(720);
+
+  if let Some(browser) = find_browser_path() {
+    let args = vec!["--new-window".into(), format!("--window-size={},{}", w, h), url.clone()];
+    AppHandle::shell(&app).command(browser).args(args).spawn().map_err(|e| e.to_string())?;
+    return Ok(());
   }
 
   // Fallback
   app.opener().open_url(url, None::<String>).map_err(|e| e.to_string())
 }
 
+#[derive(Serialize)]
+struct CdpAuthOut {
+  code: Option<String>,
+  state: Option<String>,
+}
+
+const CDP_AUTH_TIMEOUT_SECS: u64 = 300;
+
+// Opens `url` for an OAuth flow that can't rely on a loopback listener (e.g. SIMKL's
+// PIN page). Prefers driving a debuggable Chrome/Edge over CDP to auto-capture the
+// redirect; falls back to the plain sized window launch when no such browser is found
+// or the CDP session fails, in which case the caller is responsible for capturing the
+// code some other way.
+#[tauri::command]
+async fn cmd_open_oauth_window(app: AppHandle, url: String, redirect_uri: String, width: Option<u32>, height: Option<u32>) -> Result<Option<CdpAuthOut>, String> {
+  let w = width.unwrap_or(520);
+  let h = height.unwrap_or(720);
+
+  if let Some(browser) = find_browser_path() {
+    match cdp::run_cdp_auth(&browser, &url, &redirect_uri, w, h, std::time::Duration::from_secs(CDP_AUTH_TIMEOUT_SECS)).await {
+      Ok(res) => return Ok(Some(CdpAuthOut { code: res.code, state: res.state })),
+      Err(e) => eprintln!("CDP auth failed, falling back to plain window launch: {e}"),
+    }
+  }
+
+  cmd_open_external_sized(app, url, width, height).await?;
+  Ok(None)
+}
+
 // ---------- Plex PIN helpers ----------
 
 fn plex_headers(client_id: &str) -> HeaderMap {
@@ -252,117 +369,490 @@ async fn cmd_plex_poll_pin(id: i64, clientId: String) -> Result<String, String>
   let token = pin.get("auth_token").and_then(|x| x.as_str())
                 .or_else(|| pin.get("authToken").and_then(|x| x.as_str()))
                 .unwrap_or("");
+
+  // Persist directly rather than relying on the frontend to round-trip the token
+  // back through `cmd_write_config` — `PlexCfg::account_token` is `#[serde(skip)]`,
+  // so a `Config` built from IPC-provided JSON could never carry it.
+  if !token.is_empty() {
+    let mut cfg = read_cfg().map_err(|e| e.to_string())?;
+    let mut plex = cfg.plex.unwrap_or_default();
+    plex.account_token = SecretToken::some(token.to_string());
+    plex.has_account_token = true;
+    cfg.plex = Some(plex);
+    write_cfg(&cfg).map_err(|e| e.to_string())?;
+  }
+
   Ok(token.to_string())
 }
 
 // ---------- SIMKL OAuth (loopback via background task) ----------
 
+const SIMKL_LISTENER_TIMEOUT_SECS: u64 = 300;
+const SIMKL_LISTENER_POLL_MS: u64 = 250;
+
+// In-flight PKCE verifier + CSRF state for the one pending SIMKL login. Cleared once
+// the callback resolves (success, timeout, or cancellation). Tagged with a generation
+// so a listener task that's about to shut down can't race past a newer login and
+// clear its state instead of its own.
+struct PendingSimklAuth {
+  verifier: String,
+  state: String,
+  redirect_uri: String,
+  generation: u64,
+}
+
+// Validates `returned_state` against the pending login and consumes it atomically —
+// shared by the loopback callback handler and `cmd_simkl_complete_with_code` (the
+// CDP path, which validates a code/state pair with no `tiny_http::Request` to read
+// them from). The check-then-take happens under one lock acquisition so a concurrent
+// new login can't swap in a different pending entry in between.
+fn take_pending_simkl_auth(returned_state: Option<&str>) -> Result<PendingSimklAuth, &'static str> {
+  let mut guard = pending_simkl_auth().lock().unwrap();
+  let matches = match (guard.as_ref(), returned_state) {
+    (Some(pending), Some(returned)) => pending.state == returned,
+    _ => false,
+  };
+  if matches {
+    Ok(guard.take().expect("checked Some above"))
+  } else if guard.is_some() {
+    Err("state mismatch")
+  } else {
+    Err("no pending login")
+  }
+}
+
+static PENDING_SIMKL_AUTH: std::sync::OnceLock<std::sync::Mutex<Option<PendingSimklAuth>>> = std::sync::OnceLock::new();
+static SIMKL_LISTENER_GENERATION: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+fn pending_simkl_auth() -> &'static std::sync::Mutex<Option<PendingSimklAuth>> {
+  PENDING_SIMKL_AUTH.get_or_init(|| std::sync::Mutex::new(None))
+}
+
+// Cancellation flag for whichever SIMKL listener loop is currently running.
+// Replaced (not reused) each time a new listener starts.
+static SIMKL_LISTENER_CANCEL: std::sync::OnceLock<std::sync::Mutex<Option<std::sync::Arc<std::sync::atomic::AtomicBool>>>> = std::sync::OnceLock::new();
+
+fn simkl_listener_cancel() -> &'static std::sync::Mutex<Option<std::sync::Arc<std::sync::atomic::AtomicBool>>> {
+  SIMKL_LISTENER_CANCEL.get_or_init(|| std::sync::Mutex::new(None))
+}
+
+fn gen_code_verifier() -> String {
+  use rand::Rng;
+  const UNRESERVED: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-._~";
+  let mut rng = rand::thread_rng();
+  (0..64).map(|_| UNRESERVED[rng.gen_range(0..UNRESERVED.len())] as char).collect()
+}
+
+fn gen_state() -> String {
+  use rand::RngCore;
+  let mut bytes = [0u8; 16];
+  rand::thread_rng().fill_bytes(&mut bytes);
+  base64_url_nopad(&bytes)
+}
+
+fn code_challenge_s256(verifier: &str) -> String {
+  use sha2::{Digest, Sha256};
+  base64_url_nopad(&Sha256::digest(verifier.as_bytes()))
+}
+
+fn base64_url_nopad(bytes: &[u8]) -> String {
+  use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+  URL_SAFE_NO_PAD.encode(bytes)
+}
+
+#[derive(Serialize)]
+struct SimklAuthStart {
+  state: String,
+  code_challenge: String,
+  code_challenge_method: String,
+  redirect_uri: String,
+  port: u16,
+  generation: u64,
+}
+
+// Exchanges an authorization `code` for SIMKL tokens using the PKCE `verifier` from
+// the matching `PendingSimklAuth`, and persists them. Shared by the loopback
+// callback handler and `cmd_simkl_complete_with_code` (the CDP path: it observes a
+// code/state pair over DevTools Protocol with no loopback request to exchange it
+// through, so it needs the same exchange logic without a `tiny_http::Request`).
+async fn simkl_exchange_code(code: &str, verifier: &str, redirect_uri: &str) -> Result<(), String> {
+  let cfg = read_cfg().map_err(|e| format!("Config read failed: {e}"))?;
+  let Some(sim) = cfg.simkl else { return Err("SIMKL not configured".into()) };
+  if sim.client_id.is_empty() || sim.client_secret.is_empty() {
+    return Err("SIMKL client_id/secret missing".into());
+  }
+
+  let client = reqwest::Client::new();
+  let resp = client.post("https://api.simkl.com/oauth/token")
+    .header("Content-Type", "application/json")
+    .json(&serde_json::json!({
+      "client_id": sim.client_id,
+      "client_secret": sim.client_secret,
+      "grant_type": "authorization_code",
+      "redirect_uri": redirect_uri,
+      "code": code,
+      "code_verifier": verifier
+    }))
+    .send().await
+    .map_err(|_| "SIMKL token request failed".to_string())?;
+
+  if !resp.status().is_success() {
+    return Err(format!("SIMKL token exchange failed: {}", resp.status()));
+  }
+
+  #[derive(serde::Deserialize)]
+  struct TokResp { access_token: String, refresh_token: String, expires_in: i64 }
+  let tr: TokResp = resp.json().await.map_err(|_| "SIMKL token parse failed".to_string())?;
+
+  let mut cfg2 = read_cfg().unwrap_or_default();
+  let mut s = cfg2.simkl.unwrap_or_default();
+  s.access_token = SecretToken::some(tr.access_token);
+  s.has_access_token = true;
+  s.refresh_token = SecretToken::some(tr.refresh_token);
+  s.has_refresh_token = true;
+  s.token_expires_at = Some(chrono::Utc::now().timestamp() + tr.expires_in);
+  cfg2.simkl = Some(s);
+  write_cfg(&cfg2).map_err(|e| format!("Config write failed: {e}"))?;
+  Ok(())
+}
+
+// Handles a single callback request: validates state, exchanges the code, persists
+// the tokens. Returns `Ok(())` on success or `Err(message)` describing what failed;
+// either way it has already written an HTTP response to `rq`.
+async fn handle_simkl_callback(rq: tiny_http::Request, redirect_uri: &str) -> Result<(), String> {
+  use tiny_http::{Response, StatusCode};
+
+  let parsed = url::Url::parse(&format!("http://localhost{}", rq.url()))
+    .map_err(|_| "Callback parse failed".to_string());
+  let u = match parsed {
+    Ok(u) => u,
+    Err(e) => {
+      let _ = rq.respond(Response::from_string("<html><body><h3>Callback parse failed.</h3></body></html>").with_status_code(StatusCode(400)));
+      return Err(e);
+    }
+  };
+
+  let code = u.query_pairs().find(|(k, _)| k == "code").map(|(_, v)| v.to_string());
+  let returned_state = u.query_pairs().find(|(k, _)| k == "state").map(|(_, v)| v.to_string());
+
+  let Some(code) = code else {
+    let _ = rq.respond(Response::from_string("<html><body><h3>No code in callback.</h3></body></html>").with_status_code(StatusCode(400)));
+    return Err("No code in callback".into());
+  };
+
+  let pending = match take_pending_simkl_auth(returned_state.as_deref()) {
+    Ok(pending) => pending,
+    Err("state mismatch") => {
+      let _ = rq.respond(Response::from_string("<html><body><h3>State mismatch — possible CSRF, login rejected.</h3></body></html>").with_status_code(StatusCode(400)));
+      return Err("State mismatch".into());
+    }
+    Err(_) => {
+      let _ = rq.respond(Response::from_string("<html><body><h3>No pending login for this callback.</h3></body></html>").with_status_code(StatusCode(400)));
+      return Err("No pending login".into());
+    }
+  };
+
+  match simkl_exchange_code(&code, &pending.verifier, redirect_uri).await {
+    Ok(()) => {
+      let _ = rq.respond(Response::from_string("<html><body><h3>SIMKL linked. You can close this window.</h3></body></html>").with_status_code(StatusCode(200)));
+      Ok(())
+    }
+    Err(e) => {
+      let _ = rq.respond(Response::from_string(format!("<html><body><h3>SIMKL login failed: {e}</h3></body></html>")).with_status_code(StatusCode(400)));
+      Err(e)
+    }
+  }
+}
+
+// Completes a SIMKL login from a code/state pair observed out-of-band (the CDP auth
+// path in `cdp.rs`, for flows that can't use the loopback listener at all). Validates
+// `state` against the pending login exactly like the loopback callback does, then
+// reuses the same exchange logic.
+#[tauri::command]
+async fn cmd_simkl_complete_with_code(app: AppHandle, code: String, state: Option<String>) -> Result<(), String> {
+  let pending = take_pending_simkl_auth(state.as_deref()).map_err(|e| e.to_string())?;
+  simkl_exchange_code(&code, &pending.verifier, &pending.redirect_uri).await?;
+  // The loopback listener (if any) is still running and waiting on this same pending
+  // login; now that it's been completed via CDP instead, cancel it so it doesn't
+  // time out later and emit a spurious failure for a generation that already succeeded.
+  if let Some(flag) = simkl_listener_cancel().lock().unwrap().take() {
+    flag.store(true, std::sync::atomic::Ordering::SeqCst);
+  }
+  let _ = app.emit("simkl_linked", serde_json::json!({"ok": true, "generation": pending.generation}));
+  Ok(())
+}
+
 #[tauri::command]
-async fn cmd_simkl_start_listener(app: AppHandle) -> Result<(), String> {
-  use tiny_http::{Server, Response, StatusCode};
+async fn cmd_simkl_start_listener(app: AppHandle) -> Result<SimklAuthStart, String> {
+  use tiny_http::Server;
+
+  // A new login supersedes any still-pending one: explicitly cancel it so it releases
+  // its ephemeral port instead of lingering until its own 300s timeout.
+  if let Some(prev_cancel) = simkl_listener_cancel().lock().unwrap().take() {
+    prev_cancel.store(true, std::sync::atomic::Ordering::SeqCst);
+  }
+
+  let server = Server::http("127.0.0.1:0").map_err(|e| format!("Bind failed: {e}"))?;
+  let port = server.server_addr().to_ip().map(|a| a.port()).ok_or("Could not read bound port")?;
+  let redirect_uri = format!("http://127.0.0.1:{port}/callback");
 
-  let server = Server::http("127.0.0.1:8787").map_err(|e| format!("Bind 127.0.0.1:8787 failed: {e}"))?;
+  let verifier = gen_code_verifier();
+  let state = gen_state();
+  let code_challenge = code_challenge_s256(&verifier);
+  let generation = SIMKL_LISTENER_GENERATION.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+  *pending_simkl_auth().lock().unwrap() = Some(PendingSimklAuth { verifier, state: state.clone(), redirect_uri: redirect_uri.clone(), generation });
+
+  let cancel_flag = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+  *simkl_listener_cancel().lock().unwrap() = Some(cancel_flag.clone());
+
+  let task_redirect_uri = redirect_uri.clone();
   tauri::async_runtime::spawn(async move {
-    if let Ok(rq) = server.recv() {
-      let path_q = rq.url().to_string();
-      let parsed = url::Url::parse(&format!("http://localhost{}", path_q));
-      let mut ok = false;
-      let mut err: Option<String> = None;
-
-      if let Ok(u) = parsed {
-        let code = u.query_pairs().find(|(k, _)| k == "code").map(|(_, v)| v.to_string());
-        if let Some(code) = code {
-          let cfg = read_cfg();
-          match cfg {
-            Ok(cfg) => {
-              if let Some(sim) = cfg.simkl {
-                if !sim.client_id.is_empty() && !sim.client_secret.is_empty() {
-                  let client = reqwest::Client::new();
-                  let token_resp = client.post("https://api.simkl.com/oauth/token")
-                    .header("Content-Type", "application/json")
-                    .json(&serde_json::json!({
-                      "client_id": sim.client_id,
-                      "client_secret": sim.client_secret,
-                      "grant_type": "authorization_code",
-                      "redirect_uri": "http://127.0.0.1:8787/callback",
-                      "code": code
-                    }))
-                    .send().await;
-
-                  if let Ok(resp) = token_resp {
-                    if resp.status().is_success() {
-                      #[derive(serde::Deserialize)]
-                      struct TokResp { access_token: String, refresh_token: String, expires_in: i64 }
-                      if let Ok(tr) = resp.json::<TokResp>().await {
-                        let mut cfg2 = read_cfg().unwrap_or_default();
-                        let mut s = cfg2.simkl.unwrap_or_default();
-                        s.access_token = Some(tr.access_token);
-                        s.refresh_token = Some(tr.refresh_token);
-                        s.token_expires_at = Some( chrono::Utc::now().timestamp() + tr.expires_in );
-                        cfg2.simkl = Some(s);
-                        let _ = write_cfg(&cfg2);
-                        let _ = rq.respond(Response::from_string("<html><body><h3>SIMKL linked. You can close this window.</h3></body></html>").with_status_code(StatusCode(200)));
-                        ok = true;
-                      } else {
-                        let _ = rq.respond(Response::from_string("<html><body><h3>SIMKL token parse failed.</h3></body></html>").with_status_code(StatusCode(500)));
-                        err = Some("SIMKL token parse failed".into());
-                      }
-                    } else {
-                      let _ = rq.respond(Response::from_string("<html><body><h3>SIMKL token exchange failed.</h3></body></html>").with_status_code(StatusCode(400)));
-                      err = Some(format!("SIMKL token exchange failed: {}", resp.status()));
-                    }
-                  } else {
-                    let _ = rq.respond(Response::from_string("<html><body><h3>SIMKL token request failed.</h3></body></html>").with_status_code(StatusCode(500)));
-                    err = Some("SIMKL token request failed".into());
-                  }
-                } else {
-                  let _ = rq.respond(Response::from_string("<html><body><h3>SIMKL client_id/secret missing.</h3></body></html>").with_status_code(StatusCode(400)));
-                  err = Some("SIMKL client_id/secret missing".into());
-                }
-              } else {
-                let _ = rq.respond(Response::from_string("<html><body><h3>SIMKL not configured.</h3></body></html>").with_status_code(StatusCode(400)));
-                err = Some("SIMKL not configured".into());
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(SIMKL_LISTENER_TIMEOUT_SECS);
+    let mut outcome: Option<&'static str> = None;
+
+    loop {
+      if cancel_flag.load(std::sync::atomic::Ordering::SeqCst) {
+        outcome = Some("cancelled");
+        break;
+      }
+      if std::time::Instant::now() >= deadline {
+        outcome = Some("timeout");
+        break;
+      }
+      match server.recv_timeout(std::time::Duration::from_millis(SIMKL_LISTENER_POLL_MS)) {
+        Ok(Some(rq)) => {
+          if rq.url().starts_with("/callback") {
+            // Only a *successful* callback ends the listener — a spurious or failed
+            // hit (bad/missing code, state mismatch, stale duplicate) must not kill
+            // the listener before the real browser redirect arrives.
+            match handle_simkl_callback(rq, &task_redirect_uri).await {
+              Ok(()) => {
+                let _ = app.emit("simkl_linked", serde_json::json!({"ok": true, "generation": generation}));
+                outcome = Some("done");
+                break;
+              }
+              Err(e) => {
+                eprintln!("SIMKL callback attempt failed, still listening: {e}");
               }
             }
-            Err(e) => {
-              let _ = rq.respond(Response::from_string("<html><body><h3>Config read failed.</h3></body></html>").with_status_code(StatusCode(500)));
-              err = Some(format!("Config read failed: {e}"));
-            }
+          } else {
+            let _ = rq.respond(tiny_http::Response::from_string("not found").with_status_code(tiny_http::StatusCode(404)));
           }
-        } else {
-          let _ = rq.respond(Response::from_string("<html><body><h3>No code in callback.</h3></body></html>").with_status_code(StatusCode(400)));
-          err = Some("No code in callback".into());
         }
-      } else {
-        let _ = rq.respond(Response::from_string("<html><body><h3>Callback parse failed.</h3></body></html>").with_status_code(StatusCode(400)));
-        err = Some("Callback parse failed".into());
+        Ok(None) => continue,
+        Err(_) => {
+          outcome = Some("recv_error");
+          break;
+        }
       }
+    }
 
-      let _ = app.emit("simkl_linked", if ok {
-        serde_json::json!({"ok": true})
+    // Only release the cancel slot / pending login if a newer listener hasn't
+    // already taken them over — otherwise this stale task would clobber it.
+    {
+      let mut guard = simkl_listener_cancel().lock().unwrap();
+      if guard.as_ref().is_some_and(|f| std::sync::Arc::ptr_eq(f, &cancel_flag)) {
+        guard.take();
+      }
+    }
+    // Returns whether this generation's entry was still the one pending — false means
+    // it was already replaced by a newer login or consumed by cmd_simkl_complete_with_code,
+    // in which case a timeout/cancelled event for it would be stale noise, not news.
+    let clear_pending = || {
+      let mut guard = pending_simkl_auth().lock().unwrap();
+      if guard.as_ref().is_some_and(|p| p.generation == generation) {
+        guard.take();
+        true
       } else {
-        serde_json::json!({"ok": false, "error": err.unwrap_or_else(|| "Unknown error".into())})
-      });
-    } else {
-      let _ = app.emit("simkl_linked", serde_json::json!({"ok": false, "error": "Listener recv failed"}));
+        false
+      }
+    };
+    match outcome {
+      Some("timeout") => {
+        if clear_pending() {
+          let _ = app.emit("simkl_linked", serde_json::json!({"ok": false, "error": "timeout", "generation": generation}));
+        }
+      }
+      Some("cancelled") => {
+        if clear_pending() {
+          let _ = app.emit("simkl_linked", serde_json::json!({"ok": false, "error": "cancelled", "generation": generation}));
+        }
+      }
+      Some("recv_error") => {
+        let _ = app.emit("simkl_linked", serde_json::json!({"ok": false, "error": "Listener recv failed", "generation": generation}));
+      }
+      _ => {}
     }
   });
 
+  Ok(SimklAuthStart {
+    state,
+    code_challenge,
+    code_challenge_method: "S256".into(),
+    redirect_uri,
+    port,
+    generation,
+  })
+}
+
+#[tauri::command]
+async fn cmd_simkl_cancel_listener() -> Result<(), String> {
+  if let Some(flag) = simkl_listener_cancel().lock().unwrap().as_ref() {
+    flag.store(true, std::sync::atomic::Ordering::SeqCst);
+  }
   Ok(())
 }
 
+// ---------- Token refresh ----------
+
+const SIMKL_REFRESH_SKEW_SECS: i64 = 120;
+const SIMKL_REFRESH_POLL_SECS: u64 = 60;
+
+// Serializes SIMKL refreshes so the periodic timer and `cmd_run_sync` can't both
+// refresh at once and clobber each other's writes.
+static SIMKL_REFRESH_LOCK: std::sync::OnceLock<tokio::sync::Mutex<()>> = std::sync::OnceLock::new();
+
+fn simkl_refresh_lock() -> &'static tokio::sync::Mutex<()> {
+  SIMKL_REFRESH_LOCK.get_or_init(|| tokio::sync::Mutex::new(()))
+}
+
+// Refreshes the SIMKL access token if it's within `SIMKL_REFRESH_SKEW_SECS` of expiry,
+// or if no expiry is recorded (`token_expires_at` unset counts as already expired).
+// Returns whether a refresh was performed. Plex PIN tokens have no documented expiry
+// or refresh exchange, so there's nothing to schedule for that provider yet.
+async fn refresh_simkl_if_needed(app: &AppHandle) -> Result<bool, String> {
+  let _guard = simkl_refresh_lock().lock().await;
+
+  let cfg = read_cfg().map_err(|e| e.to_string())?;
+  let Some(sim) = cfg.simkl else { return Ok(false) };
+  let Some(refresh_token) = sim.refresh_token.expose().map(|s| s.to_string()) else { return Ok(false) };
+  let expires_at = sim.token_expires_at.unwrap_or(0);
+  if expires_at - chrono::Utc::now().timestamp() > SIMKL_REFRESH_SKEW_SECS {
+    return Ok(false);
+  }
+
+  let client = reqwest::Client::new();
+  let resp = client.post("https://api.simkl.com/oauth/token")
+    .header("Content-Type", "application/json")
+    .json(&serde_json::json!({
+      "client_id": sim.client_id,
+      "client_secret": sim.client_secret,
+      "grant_type": "refresh_token",
+      "refresh_token": refresh_token
+    }))
+    .send().await.map_err(|e| e.to_string())?;
+  if !resp.status().is_success() {
+    return Err(format!("SIMKL refresh failed: {}", resp.status()));
+  }
+
+  #[derive(serde::Deserialize)]
+  struct RefreshResp { access_token: String, refresh_token: Option<String>, expires_in: i64 }
+  let tr: RefreshResp = resp.json().await.map_err(|e| e.to_string())?;
+
+  let mut cfg2 = read_cfg().map_err(|e| e.to_string())?;
+  let mut s = cfg2.simkl.unwrap_or_default();
+  s.access_token = SecretToken::some(tr.access_token);
+  s.has_access_token = true;
+  if let Some(rt) = tr.refresh_token {
+    s.refresh_token = SecretToken::some(rt);
+    s.has_refresh_token = true;
+  }
+  s.token_expires_at = Some(chrono::Utc::now().timestamp() + tr.expires_in);
+  cfg2.simkl = Some(s);
+  write_cfg(&cfg2).map_err(|e| e.to_string())?;
+
+  let _ = app.emit("tokens_refreshed", serde_json::json!({"provider": "simkl"}));
+  Ok(true)
+}
+
+// Spawns the background timer that keeps SIMKL's access token fresh, refreshing once
+// immediately on startup and then every `SIMKL_REFRESH_POLL_SECS`.
+fn spawn_token_refresh_scheduler(app: AppHandle) {
+  tauri::async_runtime::spawn(async move {
+    let _ = refresh_simkl_if_needed(&app).await;
+    // `interval_at` so the first tick waits a full period instead of firing right
+    // after the startup refresh above.
+    let period = std::time::Duration::from_secs(SIMKL_REFRESH_POLL_SECS);
+    let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+    loop {
+      interval.tick().await;
+      let _ = refresh_simkl_if_needed(&app).await;
+    }
+  });
+}
+
 #[tauri::command]
-async fn cmd_run_sync(app: AppHandle) -> Result<(), String> {
+async fn cmd_refresh_tokens(app: AppHandle) -> Result<bool, String> {
+  refresh_simkl_if_needed(&app).await
+}
+
+// Monotonic id for sync runs, paired with the running child so `cmd_cancel_sync`
+// can only kill the run it was told about.
+static SYNC_RUN_ID: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+static SYNC_CHILD: std::sync::OnceLock<std::sync::Mutex<Option<(u64, tauri_plugin_shell::process::CommandChild)>>> = std::sync::OnceLock::new();
+
+fn sync_child_slot() -> &'static std::sync::Mutex<Option<(u64, tauri_plugin_shell::process::CommandChild)>> {
+  SYNC_CHILD.get_or_init(|| std::sync::Mutex::new(None))
+}
+
+#[tauri::command]
+async fn cmd_run_sync(app: AppHandle) -> Result<u64, String> {
+  use tauri_plugin_shell::process::CommandEvent;
+
+  let _ = refresh_simkl_if_needed(&app).await;
+
   let cfg = read_cfg().map_err(|e| e.to_string())?;
   write_cfg(&cfg).map_err(|e| e.to_string())?;
 
-  let mut cmd = app.shell().command("python");
-  cmd = cmd.args(["resources/python/plex_simkl_watchlist_sync.py", "--sync"]);
-  let status = cmd.status().await.map_err(|e| e.to_string())?;
-  if !status.success() {
-    return Err(format!("Sync script failed with {:?}", status.code()));
+  let run_id = SYNC_RUN_ID.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+
+  let cmd = app.shell().command("python").args(["resources/python/plex_simkl_watchlist_sync.py", "--sync"]);
+  let (mut rx, child) = cmd.spawn().map_err(|e| e.to_string())?;
+  *sync_child_slot().lock().unwrap() = Some((run_id, child));
+
+  let app2 = app.clone();
+  tauri::async_runtime::spawn(async move {
+    let mut exit_code: Option<i32> = None;
+
+    while let Some(event) = rx.recv().await {
+      match event {
+        CommandEvent::Stdout(bytes) => {
+          let line = String::from_utf8_lossy(&bytes).trim_end().to_string();
+          let parsed = serde_json::from_str::<serde_json::Value>(&line).ok();
+          let _ = app2.emit("sync_progress", serde_json::json!({"run_id": run_id, "line": line, "parsed": parsed}));
+        }
+        CommandEvent::Stderr(bytes) => {
+          let line = String::from_utf8_lossy(&bytes).trim_end().to_string();
+          let _ = app2.emit("sync_log", serde_json::json!({"run_id": run_id, "line": line}));
+        }
+        CommandEvent::Terminated(payload) => {
+          exit_code = payload.code;
+        }
+        _ => {}
+      }
+    }
+
+    let slot = sync_child_slot().lock().unwrap().take();
+    if matches!(&slot, Some((id, _)) if *id != run_id) {
+      *sync_child_slot().lock().unwrap() = slot;
+    }
+    let _ = app2.emit("sync_done", serde_json::json!({"run_id": run_id, "code": exit_code}));
+  });
+
+  Ok(run_id)
+}
+
+#[tauri::command]
+async fn cmd_cancel_sync(run_id: u64) -> Result<(), String> {
+  let slot = sync_child_slot().lock().unwrap().take();
+  match slot {
+    Some((id, child)) if id == run_id => child.kill().map_err(|e| e.to_string()),
+    Some(other) => {
+      *sync_child_slot().lock().unwrap() = Some(other);
+      Err("run_id does not match the active sync".into())
+    }
+    None => Err("no sync is running".into()),
   }
-  Ok(())
 }
 
 
@@ -422,11 +912,16 @@ fn main() {
   tauri::Builder::default()
     .plugin(tauri_plugin_opener::init())
     .plugin(tauri_plugin_shell::init())
+    .setup(|app| {
+      spawn_token_refresh_scheduler(app.handle().clone());
+      Ok(())
+    })
     .invoke_handler(tauri::generate_handler![cmd_read_config, cmd_write_config,
-      cmd_open_url, cmd_open_external_sized,
+      cmd_open_url, cmd_open_external_sized, cmd_open_oauth_window,
       cmd_plex_create_pin, cmd_plex_poll_pin,
-      cmd_simkl_start_listener,
-      cmd_run_sync, cmd_simkl_create_pin, cmd_simkl_poll_pin])
+      cmd_simkl_start_listener, cmd_simkl_cancel_listener, cmd_simkl_complete_with_code,
+      cmd_run_sync, cmd_cancel_sync, cmd_simkl_create_pin, cmd_simkl_poll_pin,
+      cmd_refresh_tokens])
     .run(tauri::generate_context!())
     .expect("error while running tauri application");
 }
@@ -0,0 +1,208 @@
+// Encrypted-at-rest storage for config secrets (Plex/SIMKL tokens).
+//
+// Tokens are stored in config.json as `enc:v1:<base64(nonce || ciphertext || tag)>`
+// using AES-256-GCM with a random 96-bit nonce per value. The data key is a
+// random 256-bit value generated on first run, stored in the OS keychain and,
+// if that's unavailable, in a key file under the user config dir with
+// restrictive permissions.
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use rand::RngCore;
+use secrecy::{ExposeSecret, SecretString};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use crate::AnyErr;
+
+const ENVELOPE_PREFIX: &str = "enc:v1:";
+const KEYRING_SERVICE: &str = "CrossWatch";
+const KEYRING_USER: &str = "config-data-key";
+const NONCE_LEN: usize = 12;
+
+// Only the successful resolution is cached — a failed lookup (e.g. the OS keyring
+// being momentarily unreachable right after login) must be retried on the next
+// encrypt/decrypt call rather than wedging every call for the rest of the process.
+static DATA_KEY: OnceLock<std::sync::Mutex<Option<[u8; 32]>>> = OnceLock::new();
+
+fn key_file_path() -> PathBuf {
+  crate::user_config_dir().join("secret.key")
+}
+
+// Records which backend actually holds the data key on first run, so a later
+// transient keyring outage can't make us think no key exists yet and silently
+// mint (and persist) a brand-new one — which would orphan every token already
+// encrypted with the real key. Once this file says "keyring", a keyring miss
+// is reported as an error instead of falling through to key generation.
+fn key_backend_marker_path() -> PathBuf {
+  crate::user_config_dir().join("secret.key.backend")
+}
+
+fn read_key_backend_marker() -> Option<String> {
+  std::fs::read_to_string(key_backend_marker_path()).ok().map(|s| s.trim().to_string())
+}
+
+fn write_key_backend_marker(backend: &str) {
+  let path = key_backend_marker_path();
+  if let Some(dir) = path.parent() {
+    let _ = std::fs::create_dir_all(dir);
+  }
+  let _ = std::fs::write(path, backend);
+}
+
+fn read_keyring_key() -> Result<[u8; 32], String> {
+  let entry = keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER).map_err(|e| format!("keyring unavailable: {e}"))?;
+  let existing = entry.get_password().map_err(|e| format!("keyring read failed: {e}"))?;
+  decode_key(&existing).ok_or_else(|| "keyring held a malformed data key".to_string())
+}
+
+fn read_key_file() -> Result<[u8; 32], String> {
+  let existing = std::fs::read_to_string(key_file_path()).map_err(|e| format!("key file read failed: {e}"))?;
+  decode_key(existing.trim()).ok_or_else(|| "key file held a malformed data key".to_string())
+}
+
+fn generate_and_store_key() -> Result<[u8; 32], String> {
+  let mut key = [0u8; 32];
+  rand::thread_rng().fill_bytes(&mut key);
+  let encoded = STANDARD.encode(key);
+
+  let stored_in_keyring = keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)
+    .and_then(|entry| entry.set_password(&encoded))
+    .is_ok();
+
+  if stored_in_keyring {
+    write_key_backend_marker("keyring");
+    return Ok(key);
+  }
+
+  let path = key_file_path();
+  if let Some(dir) = path.parent() {
+    let _ = std::fs::create_dir_all(dir);
+  }
+  std::fs::write(&path, &encoded).map_err(|e| format!("failed to persist data key to keyring or key file: {e}"))?;
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::PermissionsExt;
+    let _ = std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600));
+  }
+  write_key_backend_marker("file");
+  Ok(key)
+}
+
+fn load_or_create_key() -> Result<[u8; 32], String> {
+  match read_key_backend_marker().as_deref() {
+    Some("keyring") => return read_keyring_key(),
+    Some("file") => return read_key_file(),
+    _ => {}
+  }
+
+  // No marker yet: this is either a first run, or an upgrade from before the
+  // marker existed. Prefer whichever backend already holds a key so we don't
+  // mint a second one; only generate if neither has one.
+  if let Ok(key) = read_keyring_key() {
+    write_key_backend_marker("keyring");
+    return Ok(key);
+  }
+  if let Ok(key) = read_key_file() {
+    write_key_backend_marker("file");
+    return Ok(key);
+  }
+  generate_and_store_key()
+}
+
+fn decode_key(s: &str) -> Option<[u8; 32]> {
+  let bytes = STANDARD.decode(s).ok()?;
+  if bytes.len() != 32 {
+    return None;
+  }
+  let mut key = [0u8; 32];
+  key.copy_from_slice(&bytes);
+  Some(key)
+}
+
+fn data_key() -> Result<[u8; 32], AnyErr> {
+  let slot = DATA_KEY.get_or_init(|| std::sync::Mutex::new(None));
+  let mut slot = slot.lock().unwrap();
+  if let Some(key) = *slot {
+    return Ok(key);
+  }
+  let key = load_or_create_key()?;
+  *slot = Some(key);
+  Ok(key)
+}
+
+fn cipher() -> Result<Aes256Gcm, AnyErr> {
+  Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&data_key()?)))
+}
+
+/// True if `s` looks like one of our encrypted envelopes rather than legacy plaintext.
+pub fn is_envelope(s: &str) -> bool {
+  s.starts_with(ENVELOPE_PREFIX)
+}
+
+pub fn encrypt(plaintext: &str) -> Result<String, AnyErr> {
+  let mut nonce_bytes = [0u8; NONCE_LEN];
+  rand::thread_rng().fill_bytes(&mut nonce_bytes);
+  let nonce = Nonce::from_slice(&nonce_bytes);
+  let ciphertext = cipher()?
+    .encrypt(nonce, plaintext.as_bytes())
+    .map_err(|e| format!("token encryption failed: {e}"))?;
+
+  let mut out = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+  out.extend_from_slice(&nonce_bytes);
+  out.extend_from_slice(&ciphertext);
+  Ok(format!("{ENVELOPE_PREFIX}{}", STANDARD.encode(out)))
+}
+
+pub fn decrypt(envelope: &str) -> Result<String, AnyErr> {
+  let b64 = envelope.strip_prefix(ENVELOPE_PREFIX).ok_or("not an encrypted envelope")?;
+  let raw = STANDARD.decode(b64)?;
+  if raw.len() < NONCE_LEN {
+    return Err("encrypted envelope too short".into());
+  }
+  let (nonce_bytes, ciphertext) = raw.split_at(NONCE_LEN);
+  let plain = cipher()?
+    .decrypt(Nonce::from_slice(nonce_bytes), ciphertext)
+    .map_err(|e| format!("token decryption failed: {e}"))?;
+  Ok(String::from_utf8(plain)?)
+}
+
+/// A token field that is zeroized on drop and never printed by `Debug`.
+/// Skipped entirely by serde; `read_cfg`/`write_cfg` handle its (de)serialization
+/// explicitly so they can encrypt/decrypt the underlying envelope.
+#[derive(Clone, Default)]
+pub struct SecretToken(Option<SecretString>);
+
+impl SecretToken {
+  pub fn none() -> Self {
+    Self(None)
+  }
+
+  pub fn some(s: String) -> Self {
+    Self(Some(SecretString::from(s)))
+  }
+
+  pub fn expose(&self) -> Option<&str> {
+    self.0.as_ref().map(|s| s.expose_secret().as_str())
+  }
+
+  /// Parse a raw JSON string value for this field: decrypts known envelopes,
+  /// otherwise treats the value as legacy plaintext to migrate on next save.
+  pub fn from_raw(raw: Option<&str>) -> Result<(Self, bool), AnyErr> {
+    match raw {
+      None => Ok((Self::none(), false)),
+      Some(s) if is_envelope(s) => Ok((Self::some(decrypt(s)?), false)),
+      Some(s) => Ok((Self::some(s.to_string()), true)),
+    }
+  }
+}
+
+impl std::fmt::Debug for SecretToken {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match &self.0 {
+      Some(_) => write!(f, "SecretToken([REDACTED])"),
+      None => write!(f, "SecretToken(None)"),
+    }
+  }
+}